@@ -1,134 +1,218 @@
+use std::str::FromStr;
+
 use miniscript::{
     bitcoin::{
         hashes::{hash160, sha256, Hash}, // for to_byte_array()
         script::{Script, ScriptBuf},
-        PublicKey,
+        secp256k1::Secp256k1,
+        taproot::ControlBlock,
+        Address, PublicKey, XOnlyPublicKey,
     },
     policy::Liftable, // for .lift()
-    Miniscript, Segwitv0,
+    Legacy, Miniscript, MiniscriptKey, ScriptContext, Segwitv0, Tap,
 };
 
 fn main() {
-    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let raw_args = std::env::args().skip(1).collect::<Vec<_>>();
+    let json = raw_args.iter().any(|a| a == "--json");
+    let args: Vec<String> = raw_args.into_iter().filter(|a| a != "--json").collect();
+
     if args.is_empty() {
-        println!("NOT_LIFTABLE: missing hex argument");
-        std::process::exit(3);
+        fail(json, None, "missing hex argument");
     }
 
     let input_hex = &args[0];
-    let input_bytes = match hex::decode(input_hex) {
-        Ok(b) => b,
-        Err(e) => {
-            println!("NOT_LIFTABLE: invalid hex - {e}");
-            std::process::exit(3);
-        }
+    let spk_or_script = match hex::decode(input_hex) {
+        Ok(b) => ScriptBuf::from_bytes(b),
+        Err(hex_err) => match Address::from_str(input_hex) {
+            // Hex is tried first, so input is only parsed as an address when it is not
+            // valid hex.
+            Ok(addr) => addr.assume_checked().script_pubkey(),
+            Err(addr_err) => {
+                fail(
+                    json,
+                    None,
+                    format!("input is neither valid hex ({hex_err}) nor a valid address ({addr_err})"),
+                );
+            }
+        },
     };
-    let spk_or_script = ScriptBuf::from_bytes(input_bytes);
 
     match classify_script_pubkey(spk_or_script.as_bytes()) {
         None => {
-            // Treat as redeem/witness/tapscript (i.e., an executable script) and test liftability
-            print_liftability(spk_or_script.as_script());
+            // Not a recognized scriptPubKey template: treat as a bare executable script, which
+            // follows the same size/opcode rules as a legacy (non-segwit) script.
+            print_liftability(spk_or_script.as_script(), MsContext::Legacy, "bare", json);
         }
         Some(SpkKind::P2WSH) => {
             // Needs witnessScript to check/verify
             if args.len() < 2 {
-                println!("NOT_LIFTABLE: input is a P2WSH scriptPubKey; provide the witnessScript hex as the second argument");
-                std::process::exit(3);
+                fail(json, Some("P2WSH"), "input is a P2WSH scriptPubKey; provide the witnessScript hex as the second argument");
             }
             let ws_hex = &args[1];
             let ws = match hex_to_script(ws_hex) {
                 Ok(s) => s,
-                Err(e) => {
-                    println!("NOT_LIFTABLE: witnessScript invalid hex - {e}");
-                    std::process::exit(3);
-                }
+                Err(e) => fail(json, Some("P2WSH"), format!("witnessScript invalid hex - {e}")),
             };
             match verify_p2wsh_matches(&spk_or_script, &ws) {
-                Ok(_) => print_liftability(ws.as_script()),
-                Err(msg) => {
-                    println!("NOT_LIFTABLE: {msg}");
-                    std::process::exit(3);
-                }
+                Ok(_) => print_liftability(ws.as_script(), MsContext::Segwitv0, "P2WSH", json),
+                Err(msg) => fail(json, Some("P2WSH"), msg),
             }
         }
         Some(SpkKind::P2SH) => {
             // Needs redeemScript to check/verify
             if args.len() < 2 {
-                println!("NOT_LIFTABLE: input is a P2SH scriptPubKey; provide the redeemScript hex as the second argument");
-                std::process::exit(3);
+                fail(json, Some("P2SH"), "input is a P2SH scriptPubKey; provide the redeemScript hex as the second argument");
             }
             let rs_hex = &args[1];
             let rs = match hex_to_script(rs_hex) {
                 Ok(s) => s,
-                Err(e) => {
-                    println!("NOT_LIFTABLE: redeemScript invalid hex - {e}");
-                    std::process::exit(3);
-                }
+                Err(e) => fail(json, Some("P2SH"), format!("redeemScript invalid hex - {e}")),
             };
             match verify_p2sh_matches(&spk_or_script, &rs) {
-                Ok(_) => print_liftability(rs.as_script()),
-                Err(msg) => {
-                    println!("NOT_LIFTABLE: {msg}");
-                    std::process::exit(3);
-                }
+                // P2SH redeemScripts are evaluated under the legacy rules, not segwit's.
+                Ok(_) => print_liftability(rs.as_script(), MsContext::Legacy, "P2SH", json),
+                Err(msg) => fail(json, Some("P2SH"), msg),
             }
         }
         Some(SpkKind::P2WPKH) => {
             // No separate witnessScript exists
-            println!("NOT_LIFTABLE: input is a P2WPKH scriptPubKey; there is no separate witnessScript. Provide the actual script you want to analyze, not the scriptPubKey");
-            std::process::exit(3);
+            fail(json, Some("P2WPKH"), "input is a P2WPKH scriptPubKey; there is no separate witnessScript. Provide the actual script you want to analyze, not the scriptPubKey");
         }
         Some(SpkKind::P2TR) => {
-            // Optional: accept tapscript as second arg; verification not implemented
-            if args.len() < 2 {
-                println!("NOT_LIFTABLE: input is a P2TR (Taproot) scriptPubKey; provide the tapscript (tapleaf) hex as the second argument (SPK↔tapscript verification not implemented)");
-                std::process::exit(3);
+            // Needs the tapscript plus a control block to verify against the output key
+            if args.len() < 3 {
+                fail(json, Some("P2TR"), "input is a P2TR (Taproot) scriptPubKey; provide the tapscript (tapleaf) hex as the second argument and the control block hex as the third argument");
             }
             let ts_hex = &args[1];
             let ts = match hex_to_script(ts_hex) {
                 Ok(s) => s,
-                Err(e) => {
-                    println!("NOT_LIFTABLE: tapscript invalid hex - {e}");
-                    std::process::exit(3);
-                }
+                Err(e) => fail(json, Some("P2TR"), format!("tapscript invalid hex - {e}")),
+            };
+            let cb_hex = &args[2];
+            let control_block = match hex::decode(cb_hex) {
+                Ok(b) => b,
+                Err(e) => fail(json, Some("P2TR"), format!("control block invalid hex - {e}")),
             };
-            // We skip verifying the tapleaf against the SPK+control block; just check liftability.
-            print_liftability(ts.as_script());
+            match verify_p2tr_matches(&spk_or_script, &ts, &control_block) {
+                Ok(_) => print_liftability(ts.as_script(), MsContext::Tap, "P2TR", json),
+                Err(msg) => fail(json, Some("P2TR"), msg),
+            }
+        }
+        Some(SpkKind::WitnessUnknown(version)) => {
+            // A recognized witness program without a standard template (v2-v16, or v1 with a
+            // program length other than 32 bytes): there's no defined
+            // scriptPubKey<->script commitment to verify (no template exists yet), but the
+            // script itself can still be supplied directly and checked for liftability.
+            let kind = format!("witness_v{version}");
+            if args.len() < 2 {
+                fail(json, Some(kind.as_str()), format!("input is a v{version} witness scriptPubKey (no standard template defined yet); provide the corresponding script as the second argument to check its liftability (SPK↔script verification not implemented)"));
+            }
+            let script_hex = &args[1];
+            let script = match hex_to_script(script_hex) {
+                Ok(s) => s,
+                Err(e) => fail(json, Some(kind.as_str()), format!("script invalid hex - {e}")),
+            };
+            print_liftability(script.as_script(), MsContext::Segwitv0, &kind, json);
         }
     }
 }
 
+/// Print a NOT_LIFTABLE verdict, in plain text or (with `--json`) as a JSON object, and exit 3.
+fn fail(json: bool, input_kind: Option<&str>, msg: impl std::fmt::Display) -> ! {
+    let msg = msg.to_string();
+    if json {
+        let mut obj = serde_json::json!({ "verdict": "NOT_LIFTABLE", "error": msg });
+        if let Some(kind) = input_kind {
+            obj["input_kind"] = serde_json::Value::String(kind.to_string());
+        }
+        println!("{obj}");
+    } else {
+        println!("NOT_LIFTABLE: {msg}");
+    }
+    std::process::exit(3);
+}
+
 fn hex_to_script(hex: &str) -> Result<ScriptBuf, String> {
     let bytes = hex::decode(hex).map_err(|e| format!("invalid hex: {e}"))?;
     Ok(ScriptBuf::from_bytes(bytes))
 }
 
-fn print_liftability(script: &Script) -> ! {
-    match Miniscript::<PublicKey, Segwitv0>::parse_insane(script) {
+/// Which miniscript context (and pubkey type) a script should be parsed under.
+#[derive(Clone, Copy)]
+enum MsContext {
+    Legacy,
+    Segwitv0,
+    Tap,
+}
+
+fn print_liftability(script: &Script, ctx: MsContext, input_kind: &str, json: bool) -> ! {
+    match ctx {
+        MsContext::Legacy => print_liftability_as::<PublicKey, Legacy>(script, input_kind, json),
+        MsContext::Segwitv0 => {
+            print_liftability_as::<PublicKey, Segwitv0>(script, input_kind, json)
+        }
+        // Tapscripts use x-only keys and the Tap context (OP_CHECKSIGADD, no OP_CHECKMULTISIG,
+        // different resource limits) rather than Segwitv0.
+        MsContext::Tap => print_liftability_as::<XOnlyPublicKey, Tap>(script, input_kind, json),
+    }
+}
+
+fn print_liftability_as<Pk, Ctx>(script: &Script, input_kind: &str, json: bool) -> !
+where
+    Pk: MiniscriptKey + std::str::FromStr,
+    <Pk as std::str::FromStr>::Err: std::fmt::Display,
+    Ctx: ScriptContext,
+{
+    match Miniscript::<Pk, Ctx>::parse_insane(script) {
         Ok(ms) => {
-            println!("LIFTABLE: SAFE");
-            println!("Miniscript: {}", ms);
-            match ms.lift() {
-                Ok(policy) => println!("Policy: {}", policy),
-                Err(e) => println!("Policy: <error: {e}>"),
+            let policy = ms.lift();
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "verdict": "SAFE",
+                        "input_kind": input_kind,
+                        "miniscript": ms.to_string(),
+                        "policy": policy.as_ref().ok().map(|p| p.to_string()),
+                        "error": policy.as_ref().err().map(|e| e.to_string()),
+                    })
+                );
+            } else {
+                println!("LIFTABLE: SAFE");
+                println!("Miniscript: {}", ms);
+                match &policy {
+                    Ok(p) => println!("Policy: {}", p),
+                    Err(e) => println!("Policy: <error: {e}>"),
+                }
             }
             std::process::exit(0);
         }
-        Err(insane_err) => match Miniscript::<PublicKey, Segwitv0>::parse(script) {
+        Err(insane_err) => match Miniscript::<Pk, Ctx>::parse(script) {
             Ok(ms_unchecked) => {
-                println!("LIFTABLE: UNSAFE - {insane_err}");
-                println!("Miniscript (unchecked): {}", ms_unchecked);
-                match ms_unchecked.lift() {
-                    Ok(policy) => println!("Policy (from unchecked): {}", policy),
-                    Err(e) => println!("Policy (from unchecked): <error: {e}>"),
+                let policy = ms_unchecked.lift();
+                if json {
+                    println!(
+                        "{}",
+                        serde_json::json!({
+                            "verdict": "UNSAFE",
+                            "input_kind": input_kind,
+                            "miniscript": ms_unchecked.to_string(),
+                            "policy": policy.as_ref().ok().map(|p| p.to_string()),
+                            "error": format!("{insane_err}"),
+                        })
+                    );
+                } else {
+                    println!("LIFTABLE: UNSAFE - {insane_err}");
+                    println!("Miniscript (unchecked): {}", ms_unchecked);
+                    match &policy {
+                        Ok(p) => println!("Policy (from unchecked): {}", p),
+                        Err(e) => println!("Policy (from unchecked): <error: {e}>"),
+                    }
                 }
                 std::process::exit(1);
             }
-            Err(parse_err) => {
-                println!("NOT_LIFTABLE: {parse_err}");
-                std::process::exit(3);
-            }
+            Err(parse_err) => fail(json, Some(input_kind), parse_err),
         },
     }
 }
@@ -139,29 +223,53 @@ enum SpkKind {
     P2WSH,
     P2SH,
     P2TR,
+    /// A valid BIP141 witness program with no defined template: any v2-16 program, or a v1
+    /// program whose length is not 32 bytes.
+    WitnessUnknown(u8),
 }
 
 /// Detect common scriptPubKey patterns from raw bytes.
 fn classify_script_pubkey(b: &[u8]) -> Option<SpkKind> {
-    // v0 P2WPKH: OP_0 0x14 <20>
-    if b.len() == 22 && b[0] == 0x00 && b[1] == 0x14 {
-        return Some(SpkKind::P2WPKH);
-    }
-    // v0 P2WSH: OP_0 0x20 <32>
-    if b.len() == 34 && b[0] == 0x00 && b[1] == 0x20 {
-        return Some(SpkKind::P2WSH);
+    if let Some((version, program)) = parse_witness_program(b) {
+        return Some(match (version, program.len()) {
+            (0, 20) => SpkKind::P2WPKH,
+            (0, 32) => SpkKind::P2WSH,
+            (1, 32) => SpkKind::P2TR,
+            // v0 must be exactly 20 or 32 bytes (BIP141); anything else isn't a witness
+            // program, so fall through to the non-witness checks below.
+            (0, _) => return classify_non_witness_script_pubkey(b),
+            (v, _) => SpkKind::WitnessUnknown(v),
+        });
     }
+    classify_non_witness_script_pubkey(b)
+}
+
+fn classify_non_witness_script_pubkey(b: &[u8]) -> Option<SpkKind> {
     // P2SH: OP_HASH160 OP_PUSHBYTES_20 <20> OP_EQUAL
     if b.len() == 23 && b[0] == 0xa9 && b[1] == 0x14 && b[22] == 0x87 {
         return Some(SpkKind::P2SH);
     }
-    // Taproot (v1): OP_1 0x20 <32>
-    if b.len() == 34 && b[0] == 0x51 && b[1] == 0x20 {
-        return Some(SpkKind::P2TR);
-    }
     None
 }
 
+/// Parse a BIP141 witness program: a version opcode (`OP_0` or `OP_PUSHNUM_1..OP_PUSHNUM_16`)
+/// followed by a single push of the 2-40 byte program. Returns `(version, program)`.
+fn parse_witness_program(b: &[u8]) -> Option<(u8, &[u8])> {
+    let version = match *b.first()? {
+        0x00 => 0u8,             // OP_0
+        op @ 0x51..=0x60 => op - 0x50, // OP_PUSHNUM_1..OP_PUSHNUM_16
+        _ => return None,
+    };
+    let push_len = *b.get(1)? as usize;
+    if push_len < 2 || push_len > 40 {
+        return None;
+    }
+    if b.len() != 2 + push_len {
+        return None;
+    }
+    Some((version, &b[2..]))
+}
+
 /// Verify that `witness_script` matches the given P2WSH scriptPubKey.
 fn verify_p2wsh_matches(spk: &ScriptBuf, witness_script: &ScriptBuf) -> Result<(), String> {
     let b = spk.as_bytes();
@@ -177,6 +285,32 @@ fn verify_p2wsh_matches(spk: &ScriptBuf, witness_script: &ScriptBuf) -> Result<(
     }
 }
 
+/// Verify that `tapscript` matches the given P2TR scriptPubKey for the supplied control block
+/// (BIP341): the control block commits the leaf (via the TapLeaf tagged hash) through a merkle
+/// path of TapBranch tagged hashes to a merkle root, which is then used to tweak the internal
+/// key into the output key that must equal the witness program.
+fn verify_p2tr_matches(
+    spk: &ScriptBuf,
+    tapscript: &ScriptBuf,
+    control_block: &[u8],
+) -> Result<(), String> {
+    let b = spk.as_bytes();
+    if b.len() != 34 || b[0] != 0x51 || b[1] != 0x20 {
+        return Err("not a P2TR scriptPubKey".to_string());
+    }
+    let output_key = XOnlyPublicKey::from_slice(&b[2..34])
+        .map_err(|e| format!("invalid P2TR output key in scriptPubKey: {e}"))?;
+    let control_block = ControlBlock::decode(control_block)
+        .map_err(|e| format!("invalid control block: {e}"))?;
+    let secp = Secp256k1::verification_only();
+    if control_block.verify_taproot_commitment(&secp, output_key, tapscript.as_script()) {
+        Ok(())
+    } else {
+        Err("tapscript does not match P2TR witness program for the given control block"
+            .to_string())
+    }
+}
+
 /// Verify that `redeem_script` matches the given P2SH scriptPubKey.
 fn verify_p2sh_matches(spk: &ScriptBuf, redeem_script: &ScriptBuf) -> Result<(), String> {
     let b = spk.as_bytes();